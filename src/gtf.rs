@@ -89,7 +89,7 @@ impl FromStr for Attributes {
 
 #[derive(Debug)]
 pub struct GTFRecord {
-    // pub chromosome_name: String,
+    pub chromosome_name: String,
     // pub annotation_source: String,
     pub feature_type: FeatureType,
     pub start: usize,
@@ -112,7 +112,11 @@ impl FromStr for GTFRecord {
         let err: Result<Self> = Err(anyhow!("Syntax error, not valid GENCODE GTF: \"{s}\""));
 
         Ok(Self {
-            feature_type: match cols.nth(2) {
+            chromosome_name: match cols.next() {
+                Some(s) => s.to_string(),
+                None => return err,
+            },
+            feature_type: match cols.nth(1) {
                 Some(s) => s.parse::<FeatureType>()?,
                 None => return err,
             },
@@ -145,13 +149,22 @@ impl GTFRecord {
     }
 }
 
-pub fn read_gtf_file(annotations: &Path) -> Result<Vec<GTFRecord>> {
+/// Parses a GTF/GTF.gz file, keeping only `transcript`/`start_codon`/`exon`/
+/// `CDS` records for protein-coding genes (the first two drive promoter
+/// finding, the latter two feed `FeatureIndex`). If `chrom` is given, only
+/// records on that contig are kept; otherwise every contig present in the
+/// file is kept.
+pub fn read_gtf_file(annotations: &Path, chrom: Option<&str>) -> Result<Vec<GTFRecord>> {
     let reader = MaybeCompressedReader::new(annotations)?;
 
     // Select the lines we need with a regex first, and parse later (for performance reasons)
-    let regex = Regex::new(
-        r"^chr1\t(?:HAVANA|ENSEMBL)\t(?:transcript|start_codon).*gene_type..protein_coding.;",
-    )
+    let chrom_pattern = match chrom {
+        Some(chrom) => regex::escape(chrom),
+        None => r"\S+".to_string(),
+    };
+    let regex = Regex::new(&format!(
+        r"^{chrom_pattern}\t(?:HAVANA|ENSEMBL)\t(?:transcript|start_codon|exon|CDS).*gene_type..protein_coding.;"
+    ))
     .unwrap();
 
     let records: Result<Vec<GTFRecord>> = reader
@@ -188,6 +201,105 @@ pub fn get_longest_transcripts(gtf_records: &[GTFRecord]) -> Result<Vec<&GTFReco
     Ok(longest_transcripts)
 }
 
+/// A static index over a set of `GTFRecord`s that answers "which features
+/// overlap region `[start, end)`" without a linear scan.
+///
+/// Intervals are sorted by start position and stored in a flat array next to
+/// a running "maximum end seen so far", so a query can binary-search for the
+/// first interval that could possibly overlap (skipping the prefix whose
+/// maximum end is below `start`), then walk forward until intervals start at
+/// or after `end`, without visiting the rest of the array (a flat sorted
+/// array rather than a pointer-based tree).
+pub struct FeatureIndex<'a> {
+    entries: Vec<IndexEntry<'a>>,
+}
+
+struct IndexEntry<'a> {
+    start: usize,
+    end: usize,
+    max_end: usize, // max `end` among entries[0..=self]
+    record: &'a GTFRecord,
+}
+
+impl<'a> FeatureIndex<'a> {
+    /// Builds an index over `records`, which must all share a single
+    /// chromosome (the `max_end` pruning below doesn't carry a contig, so
+    /// mixing contigs would let a query on one match coordinates on
+    /// another). Use `build_per_chrom` when `records` spans more than one.
+    pub fn build(records: &'a [GTFRecord]) -> Self {
+        let mut entries: Vec<_> = records
+            .iter()
+            .map(|record| IndexEntry {
+                start: record.start,
+                end: record.end,
+                max_end: 0,
+                record,
+            })
+            .collect();
+
+        entries.sort_by_key(|e| e.start);
+
+        let mut running_max_end = 0;
+        for entry in entries.iter_mut() {
+            running_max_end = running_max_end.max(entry.end);
+            entry.max_end = running_max_end;
+        }
+
+        Self { entries }
+    }
+
+    /// Groups `records` by `chromosome_name` and builds one `FeatureIndex`
+    /// per contig, so a query against one chromosome can never match a
+    /// feature on another.
+    pub fn build_per_chrom(records: &'a [GTFRecord]) -> HashMap<&'a str, Self> {
+        let mut by_chrom: HashMap<&'a str, Vec<&'a GTFRecord>> = HashMap::new();
+        for record in records {
+            by_chrom
+                .entry(record.chromosome_name.as_str())
+                .or_default()
+                .push(record);
+        }
+
+        by_chrom
+            .into_iter()
+            .map(|(chrom, records)| {
+                let mut entries: Vec<_> = records
+                    .into_iter()
+                    .map(|record| IndexEntry {
+                        start: record.start,
+                        end: record.end,
+                        max_end: 0,
+                        record,
+                    })
+                    .collect();
+
+                entries.sort_by_key(|e| e.start);
+
+                let mut running_max_end = 0;
+                for entry in entries.iter_mut() {
+                    running_max_end = running_max_end.max(entry.end);
+                    entry.max_end = running_max_end;
+                }
+
+                (chrom, Self { entries })
+            })
+            .collect()
+    }
+
+    /// Returns every record overlapping `[start, end)`.
+    pub fn query(&self, start: usize, end: usize) -> impl Iterator<Item = &GTFRecord> + '_ {
+        // Everything before `first` has a max-end below `start`, so none of
+        // them (or anything they dominate) can overlap the query.
+        let first = self.entries.partition_point(|e| e.max_end < start);
+
+        self.entries[first..]
+            .iter()
+            .take_while(move |e| e.start < end)
+            .filter(move |e| e.end > start)
+            .map(|e| e.record)
+    }
+}
+
 /// Reader for a file that could be gzip compressed or not
 struct MaybeCompressedReader;
 
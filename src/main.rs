@@ -1,14 +1,19 @@
 mod gc_content;
 mod gtf;
+mod motif;
+mod npy;
 mod plots;
+mod signal;
+mod thermo;
 
 use anyhow::{anyhow, Result};
-use bigtools::{bigwigread::BigWigRead, seekableread::ReopenableFile};
 use bio::io::fasta;
 use clap::Parser;
+use ndarray::Array2;
 use regex::Regex;
+use signal::SignalSource;
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     fs::File,
     path::{Path, PathBuf},
 };
@@ -16,15 +21,30 @@ use std::{
 #[derive(Parser)]
 #[clap(author)]
 struct Cli {
-    /// File contatining a sequence for a single chromosome, in FASTA format (.fa/.fasta)
+    /// File contatining the assembly sequence, in FASTA format (.fa/.fasta).
+    /// May contain more than one contig.
     sequence: PathBuf,
 
     /// File contatining sequence annotations in (gz compressed) GTF format (.gtf/.gtf.gz)
     annotations: PathBuf,
 
-    /// File containing MNase-seq data in BigWig format (.bigWig)
+    /// Restrict processing to this contig (e.g. "chr1"). If omitted, every
+    /// contig present in both the FASTA and GTF inputs is processed; a
+    /// contig missing from the MNase-seq input is an error when its signal
+    /// is queried
+    #[clap(long)]
+    chrom: Option<String>,
+
+    /// File containing MNase-seq data, either pre-computed in BigWig format
+    /// (.bigWig) or as aligned reads (.bam/.cram), from which the signal is
+    /// computed on the fly from fragment midpoints
     mnase_seq: String,
 
+    /// Smooth the BAM/CRAM-derived signal with a Gaussian kernel of this
+    /// bandwidth (in bases). Ignored for BigWig input.
+    #[clap(long)]
+    mnase_smoothing_bandwidth: Option<f64>,
+
     /// Plot the GC content for the whole chromosome
     #[clap(long)]
     total_gc: bool,
@@ -33,6 +53,19 @@ struct Cli {
     #[clap(long)]
     promotor_gc: bool,
 
+    /// Plot the average nearest-neighbor melting temperature of all
+    /// promotor regions, and annotate discovered TFBS/motif hits with it
+    #[clap(long)]
+    promotor_tm: bool,
+
+    /// Total strand concentration (mol/L) for melting-temperature calculations
+    #[clap(long, default_value_t = 2.5e-7)]
+    strand_molarity: f64,
+
+    /// Monovalent cation concentration (mol/L) for melting-temperature calculations
+    #[clap(long, default_value_t = 0.05)]
+    salt_molarity: f64,
+
     /// Plot the average Nucleosome affinity of of all promotor regions
     #[clap(long)]
     promotor_nsome_affinity: bool,
@@ -40,72 +73,141 @@ struct Cli {
     /// Plot the average Nucleosome affinity of the TFBS
     #[clap(long)]
     tfbs_nsome_affinity: bool,
+
+    /// Score promoter sequences against PWMs loaded from this JASPAR/MEME
+    /// count-matrix file, and plot one nucleosome-affinity curve per matrix
+    /// (independently of the hardcoded AP-1/NF-Y regexes, which run only
+    /// with --tfbs-nsome-affinity)
+    #[clap(long)]
+    motif: Option<PathBuf>,
+
+    /// Export the full per-position signal matrix of each enabled analysis
+    /// as NumPy .npy files into this directory, for downstream stats in
+    /// Python/pandas
+    #[clap(long)]
+    export_npy: Option<PathBuf>,
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
 
-    let fasta_record = read_fasta_record(&cli.sequence)?;
+    let fasta_records = read_fasta_records(&cli.sequence)?;
 
     if cli.total_gc {
-        plots::plot1(&fasta_record, 5000)?;
+        let fasta_record = select_chrom(&fasta_records, cli.chrom.as_deref())?;
+        plots::plot1(fasta_record, 5000)?;
         return Ok(());
     }
 
-    let records = gtf::read_gtf_file(&cli.annotations)?;
+    let records = gtf::read_gtf_file(&cli.annotations, cli.chrom.as_deref())?;
     let longest_transcripts = gtf::get_longest_transcripts(&records)?;
-    let promotors = find_promotor_regions(&fasta_record, &records, longest_transcripts);
+    let promotors = find_promotor_regions(&fasta_records, &records, longest_transcripts);
+
+    if promotors.is_empty() {
+        eprintln!("No protein-coding start codons found, nothing to analyze");
+        return Ok(());
+    }
 
     if cli.promotor_gc {
-        let mut total_gc: Vec<(usize, f64)> = vec![(0, 0.0); promotors[0].sequence.len() - 149];
+        let window_count = promotors[0].sequence.len() - 149;
+        let mut gc_matrix = Array2::from_elem((promotors.len(), window_count), f64::NAN);
 
-        for p in &promotors {
+        for (row, p) in promotors.iter().enumerate() {
             let sequence = if p.strand == gtf::Strand::Minus {
                 p.get_opposite_sequence()
             } else {
                 p.sequence.clone()
             };
 
-            let gc: Vec<_> = gc_content::get_gc_content(&sequence, 150, 1).collect();
-
-            for ((i, x), (j, y)) in total_gc.iter_mut().zip(gc.iter()) {
-                *i = *j;
-                *x += y;
+            for (col, (_, gc)) in gc_content::get_gc_content(&sequence, 150, 1).enumerate() {
+                gc_matrix[[row, col]] = gc;
             }
         }
 
-        let total_gc: Vec<_> = total_gc
-            .into_iter()
-            .map(|(i, x)| (i, x / promotors.len() as f64))
+        if let Some(dir) = &cli.export_npy {
+            let offsets: Vec<i64> = (0..window_count as i64).map(|i| i + 75).collect();
+            npy::write_matrix(dir, "promotor_gc", &gc_matrix, &offsets)?;
+        }
+
+        let total_gc: Vec<(usize, f64)> = (0..window_count)
+            .map(|col| (col + 75, npy::column_mean(&gc_matrix, col)))
             .collect();
 
         plots::plot2(&total_gc)?;
         return Ok(());
     }
 
-    let mut big_wig_reader = BigWigRead::from_file_and_attach(cli.mnase_seq.as_str()).unwrap();
+    if cli.promotor_tm {
+        let window_size = 20;
+        let window_count = promotors[0].sequence.len() - (window_size - 1);
+        let mut tm_matrix = Array2::from_elem((promotors.len(), window_count), f64::NAN);
+
+        for (row, p) in promotors.iter().enumerate() {
+            let sequence = if p.strand == gtf::Strand::Minus {
+                p.get_opposite_sequence()
+            } else {
+                p.sequence.clone()
+            };
+
+            for (center, tm) in thermo::get_melting_temp(
+                &sequence,
+                window_size,
+                1,
+                cli.strand_molarity,
+                cli.salt_molarity,
+            ) {
+                tm_matrix[[row, center - window_size / 2]] = tm;
+            }
+        }
+
+        if let Some(dir) = &cli.export_npy {
+            let offsets: Vec<i64> = (0..window_count as i64)
+                .map(|i| i + window_size as i64 / 2)
+                .collect();
+            npy::write_matrix(dir, "promotor_tm", &tm_matrix, &offsets)?;
+        }
+
+        let total_tm: Vec<(usize, f64)> = (0..window_count)
+            .map(|col| (col + window_size / 2, npy::column_mean(&tm_matrix, col)))
+            .collect();
+
+        plots::plot5(&total_tm)?;
+    }
+
+    let mut signal_source = signal::open_signal_source(
+        &cli.mnase_seq,
+        Some(&cli.sequence),
+        cli.mnase_smoothing_bandwidth,
+    )?;
 
     if cli.promotor_nsome_affinity {
-        let mut total_affinity = [(0u32, 0.0); 1100];
+        let mut affinity_matrix = Array2::from_elem((promotors.len(), 1100), f64::NAN);
 
-        for p in &promotors {
-            let mut affinity =
-                big_wig_reader.values("chr1", p.location as u32, p.location as u32 + 1100)?;
+        for (row, p) in promotors.iter().enumerate() {
+            let mut affinity = signal_source.values(
+                &p.chromosome_name,
+                p.location as u32,
+                p.location as u32 + 1100,
+            )?;
 
             if p.strand == gtf::Strand::Minus {
                 affinity.reverse();
             }
 
-            for (i, v) in affinity.iter().enumerate() {
+            for (col, v) in affinity.iter().enumerate() {
                 if !v.is_nan() {
-                    total_affinity[i].1 += *v as f64;
-                    total_affinity[i].0 += 1;
+                    affinity_matrix[[row, col]] = *v as f64;
                 }
             }
         }
 
-        let avg_affinity =
-            (-1000..100).zip(total_affinity.iter().map(|&(count, v)| v / count as f64));
+        if let Some(dir) = &cli.export_npy {
+            let offsets: Vec<i64> = (-1000..100).collect();
+            npy::write_matrix(dir, "promotor_affinity", &affinity_matrix, &offsets)?;
+        }
+
+        let avg_affinity = (-1000..100)
+            .zip((0..1100).map(|col| npy::column_mean(&affinity_matrix, col)));
 
         plots::plot3(avg_affinity)?;
         return Ok(());
@@ -115,29 +217,205 @@ fn main() -> Result<()> {
         let regex_ap_1 = regex::bytes::Regex::new(r"TGA(C|G)TCA").unwrap();
         let regex_nf_y = regex::bytes::Regex::new(r"(CCAAT|ATTGG)").unwrap();
 
-        let ap_1 = get_tfbs_avg_nsome_affinity(&regex_ap_1, &promotors, &mut big_wig_reader)?;
+        let feature_index = gtf::FeatureIndex::build_per_chrom(&records);
+        let tfbs_offsets: Vec<i64> = (-500..501).collect();
+
+        let ap_1_matrix = get_tfbs_avg_nsome_affinity(
+            &regex_ap_1,
+            &promotors,
+            &feature_index,
+            signal_source.as_mut(),
+            &cli,
+        )?;
+        if let Some(dir) = &cli.export_npy {
+            npy::write_matrix(dir, "ap1_affinity", &ap_1_matrix, &tfbs_offsets)?;
+        }
+        let ap_1 = (-500..501)
+            .zip((0..1001).map(|col| npy::column_mean(&ap_1_matrix, col)))
+            .collect();
         plots::plot4(ap_1, "AP-1")?;
 
-        let nf_y = get_tfbs_avg_nsome_affinity(&regex_nf_y, &promotors, &mut big_wig_reader)?;
+        let nf_y_matrix = get_tfbs_avg_nsome_affinity(
+            &regex_nf_y,
+            &promotors,
+            &feature_index,
+            signal_source.as_mut(),
+            &cli,
+        )?;
+        if let Some(dir) = &cli.export_npy {
+            npy::write_matrix(dir, "nfy_affinity", &nf_y_matrix, &tfbs_offsets)?;
+        }
+        let nf_y = (-500..501)
+            .zip((0..1001).map(|col| npy::column_mean(&nf_y_matrix, col)))
+            .collect();
         plots::plot4(nf_y, "NF-Y")?;
     }
 
+    if let Some(motif_file) = &cli.motif {
+        let feature_index = gtf::FeatureIndex::build_per_chrom(&records);
+        let pfms = motif::load_jaspar_pfms(motif_file)?;
+
+        for (name, counts) in pfms {
+            let pwm = motif::Pwm::from_counts(name.clone(), counts, 0.8, [0.25; 4]);
+            let threshold = pwm.score_threshold_for_p_value([0.25; 4], 0.0001, 100.0);
+
+            let matrix = get_motif_avg_nsome_affinity(
+                &pwm,
+                threshold,
+                &promotors,
+                &feature_index,
+                signal_source.as_mut(),
+                &cli,
+            )?;
+
+            if let Some(dir) = &cli.export_npy {
+                let offsets: Vec<i64> = (-500..501).collect();
+                npy::write_matrix(dir, &format!("{name}_affinity"), &matrix, &offsets)?;
+            }
+
+            let avg_affinity = (-500..501)
+                .zip((0..1001).map(|col| npy::column_mean(&matrix, col)))
+                .collect();
+            plots::plot4(avg_affinity, &name)?;
+        }
+    }
+
     Ok(())
 }
 
+/// Mirrors `get_tfbs_avg_nsome_affinity`, but scores promoter sequences
+/// against a PWM instead of matching a regex.
+fn get_motif_avg_nsome_affinity(
+    pwm: &motif::Pwm,
+    threshold: f64,
+    promotors: &[PromotorRegion],
+    feature_index: &HashMap<&str, gtf::FeatureIndex>,
+    signal_source: &mut dyn SignalSource,
+    cli: &Cli,
+) -> Result<Array2<f64>> {
+    let mut rows: Vec<Vec<f64>> = Vec::new();
+
+    for p in promotors {
+        for hit in pwm.scan(&p.sequence, threshold) {
+            let hit_start = p.location + hit.center_position - pwm.len() / 2;
+            let hit_end = hit_start + pwm.len();
+
+            if overlaps_exon_or_cds(feature_index, &p.chromosome_name, hit_start, hit_end) {
+                continue;
+            }
+
+            if cli.promotor_tm {
+                let window = hit_start - p.location..hit_end - p.location;
+                if let Ok(tm) = thermo::nearest_neighbor_tm(
+                    &p.sequence[window],
+                    cli.strand_molarity,
+                    cli.salt_molarity,
+                ) {
+                    println!(
+                        "{} {pwm_name} hit at {hit_start}-{hit_end}: Tm {tm:.1}°C",
+                        p.chromosome_name,
+                        pwm_name = pwm.name
+                    );
+                }
+            }
+
+            let center = p.location + hit.center_position;
+
+            if center < 500 {
+                eprintln!(
+                    "Skipping {pwm_name} hit too close to the start of \"{}\"",
+                    p.chromosome_name,
+                    pwm_name = pwm.name
+                );
+                continue;
+            }
+
+            let affinity = signal_source.values(
+                &p.chromosome_name,
+                center as u32 - 500,
+                center as u32 + 501,
+            )?;
+
+            rows.push(affinity.into_iter().map(|v| v as f64).collect());
+        }
+    }
+
+    let mut matrix = Array2::from_elem((rows.len(), 1001), f64::NAN);
+    for (row, values) in rows.into_iter().enumerate() {
+        for (col, v) in values.into_iter().enumerate() {
+            matrix[[row, col]] = v;
+        }
+    }
+
+    Ok(matrix)
+}
+
+/// Skip motif hits that fall inside an exon or CDS, since those are part of
+/// the transcribed/coding sequence rather than regulatory promoter sequence.
+/// A contig absent from `feature_index` (no annotated features) never
+/// overlaps anything.
+fn overlaps_exon_or_cds(
+    feature_index: &HashMap<&str, gtf::FeatureIndex>,
+    chrom: &str,
+    start: usize,
+    end: usize,
+) -> bool {
+    feature_index
+        .get(chrom)
+        .map(|index| {
+            index
+                .query(start, end)
+                .any(|r| matches!(r.feature_type, gtf::FeatureType::Exon | gtf::FeatureType::CDS))
+        })
+        .unwrap_or(false)
+}
+
+/// Builds the stacked nucleosome-affinity matrix (one row per motif hit, one
+/// column per base offset) for every occurrence of `regex` across all
+/// promoters, skipping hits inside exons/CDS.
 fn get_tfbs_avg_nsome_affinity(
     regex: &regex::bytes::Regex,
     promotors: &[PromotorRegion],
-    big_wig_reader: &mut BigWigRead<ReopenableFile, File>,
-) -> Result<Vec<(i32, f64)>> {
-    let mut total_affinity = vec![(0, 0.0); 1001];
+    feature_index: &HashMap<&str, gtf::FeatureIndex>,
+    signal_source: &mut dyn SignalSource,
+    cli: &Cli,
+) -> Result<Array2<f64>> {
+    let mut rows: Vec<Vec<f64>> = Vec::new();
 
     for p in promotors {
         for m in regex.find_iter(&p.sequence) {
+            let tfbs_start = p.location + m.start();
+            let tfbs_end = p.location + m.end();
+
+            if overlaps_exon_or_cds(feature_index, &p.chromosome_name, tfbs_start, tfbs_end) {
+                continue;
+            }
+
+            if cli.promotor_tm {
+                if let Ok(tm) = thermo::nearest_neighbor_tm(
+                    m.as_bytes(),
+                    cli.strand_molarity,
+                    cli.salt_molarity,
+                ) {
+                    println!(
+                        "{} TFBS hit at {tfbs_start}-{tfbs_end}: Tm {tm:.1}°C",
+                        p.chromosome_name
+                    );
+                }
+            }
+
             let tfbs_center = p.location + ((m.end() - m.start()) / 2);
 
-            let mut affinity = big_wig_reader.values(
-                "chr1",
+            if tfbs_center < 500 {
+                eprintln!(
+                    "Skipping TFBS hit too close to the start of \"{}\"",
+                    p.chromosome_name
+                );
+                continue;
+            }
+
+            let affinity = signal_source.values(
+                &p.chromosome_name,
                 tfbs_center as u32 - 500,
                 tfbs_center as u32 + 501,
             )?;
@@ -146,24 +424,23 @@ fn get_tfbs_avg_nsome_affinity(
             //     affinity.reverse();
             // }
 
-            for (i, v) in affinity.iter().enumerate() {
-                if !v.is_nan() {
-                    total_affinity[i].1 += *v as f64;
-                    total_affinity[i].0 += 1;
-                }
-            }
+            rows.push(affinity.into_iter().map(|v| v as f64).collect());
         }
     }
-    
-    println!("{total_affinity:#?}");
 
-    let avg_affinity = (-500..501).zip(total_affinity.iter().map(|&(count, v)| v / count as f64)).collect();
+    let mut matrix = Array2::from_elem((rows.len(), 1001), f64::NAN);
+    for (row, values) in rows.into_iter().enumerate() {
+        for (col, v) in values.into_iter().enumerate() {
+            matrix[[row, col]] = v;
+        }
+    }
 
-    Ok(avg_affinity)
+    Ok(matrix)
 }
 
 #[derive(Debug)]
 struct PromotorRegion {
+    chromosome_name: String,
     sequence: Vec<u8>,
     location: usize, // first index in fasta file, ignoring direction
     strand: gtf::Strand,
@@ -185,8 +462,11 @@ impl PromotorRegion {
     }
 }
 
+/// Finds the promotor region of every given transcript's start codon,
+/// skipping any whose contig isn't present in `fasta_records` (e.g. a
+/// scaffold only annotated, not assembled).
 fn find_promotor_regions(
-    fasta_record: &fasta::Record,
+    fasta_records: &HashMap<String, fasta::Record>,
     records: &[gtf::GTFRecord],
     transcripts: Vec<&gtf::GTFRecord>,
 ) -> Vec<PromotorRegion> {
@@ -204,20 +484,59 @@ fn find_promotor_regions(
     // ATG is at 1000, CAT at 98
     // in fasta: ATG at p.start+1000, CAT at p.end+98
     for start_codon in &start_codons {
+        let Some(fasta_record) = fasta_records.get(&start_codon.chromosome_name) else {
+            continue;
+        };
+        let contig_len = fasta_record.seq().len();
+
         if start_codon.strand == gtf::Strand::Plus {
+            if start_codon.start < 1001 {
+                eprintln!(
+                    "Skipping start codon too close to the start of \"{}\"",
+                    start_codon.chromosome_name
+                );
+                continue;
+            }
+
             let start = start_codon.start - 1001;
             let end = start_codon.start + 100;
 
+            if end > contig_len {
+                eprintln!(
+                    "Skipping start codon too close to the end of \"{}\"",
+                    start_codon.chromosome_name
+                );
+                continue;
+            }
+
             promotors.push(PromotorRegion {
+                chromosome_name: start_codon.chromosome_name.clone(),
                 sequence: fasta_record.seq()[start..end].to_vec(),
                 location: start,
                 strand: gtf::Strand::Plus,
             });
         } else {
+            if start_codon.end < 101 {
+                eprintln!(
+                    "Skipping start codon too close to the start of \"{}\"",
+                    start_codon.chromosome_name
+                );
+                continue;
+            }
+
             let start = start_codon.end + 1000;
             let end = start_codon.end - 101;
 
+            if start > contig_len {
+                eprintln!(
+                    "Skipping start codon too close to the end of \"{}\"",
+                    start_codon.chromosome_name
+                );
+                continue;
+            }
+
             promotors.push(PromotorRegion {
+                chromosome_name: start_codon.chromosome_name.clone(),
                 sequence: fasta_record.seq()[end..start].to_vec(),
                 location: end,
                 strand: gtf::Strand::Minus,
@@ -233,18 +552,37 @@ fn find_promotor_regions(
     promotors
 }
 
-fn read_fasta_record(path: &Path) -> Result<fasta::Record> {
+/// Parses every record of a (possibly multi-contig) FASTA file into a map
+/// keyed by contig name.
+fn read_fasta_records(path: &Path) -> Result<HashMap<String, fasta::Record>> {
     let fasta_file = File::open(path)?;
-    let mut fasta_records = fasta::Reader::new(fasta_file).records();
+    let records: Result<HashMap<String, fasta::Record>, _> = fasta::Reader::new(fasta_file)
+        .records()
+        .map(|rec| rec.map(|rec| (rec.id().to_string(), rec)))
+        .collect();
 
-    match fasta_records.next() {
-        Some(rec) => {
-            if fasta_records.next().is_none() {
-                Ok(rec?)
-            } else {
-                Err(anyhow!("Error: FASTA file has more than one record"))
-            }
-        }
-        None => Err(anyhow!("Error: FASTA file has no records")),
+    let records = records?;
+
+    if records.is_empty() {
+        return Err(anyhow!("Error: FASTA file has no records"));
+    }
+
+    Ok(records)
+}
+
+/// Picks a single FASTA record by contig name, or the only record present
+/// if there is exactly one and no name was given.
+fn select_chrom<'a>(
+    fasta_records: &'a HashMap<String, fasta::Record>,
+    chrom: Option<&str>,
+) -> Result<&'a fasta::Record> {
+    match chrom {
+        Some(chrom) => fasta_records
+            .get(chrom)
+            .ok_or_else(|| anyhow!("Chromosome \"{chrom}\" not found in FASTA file")),
+        None if fasta_records.len() == 1 => Ok(fasta_records.values().next().unwrap()),
+        None => Err(anyhow!(
+            "FASTA file has multiple contigs, specify one with --chrom"
+        )),
     }
 }
@@ -0,0 +1,256 @@
+//! Position-weight-matrix (PWM/PSSM) motif scanning from JASPAR-style count
+//! matrices.
+
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strand {
+    Plus,
+    Minus,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MotifHit {
+    pub center_position: usize,
+    pub strand: Strand,
+    pub score: f64,
+}
+
+/// A log-odds position-specific scoring matrix built from a position
+/// frequency/count matrix.
+pub struct Pwm {
+    pub name: String,
+    /// log-odds scores, `scores[position][base]`, base order A,C,G,T.
+    scores: Vec<[f64; 4]>,
+}
+
+impl Pwm {
+    /// Converts per-position base counts into a log-odds PSSM against
+    /// `background` frequencies, adding `pseudocount` to avoid -inf scores.
+    pub fn from_counts(
+        name: String,
+        counts: Vec<[u32; 4]>,
+        pseudocount: f64,
+        background: [f64; 4],
+    ) -> Self {
+        let scores = counts
+            .into_iter()
+            .map(|column| {
+                let total = column.iter().sum::<u32>() as f64 + 4.0 * pseudocount;
+                let mut column_scores = [0.0; 4];
+                for base in 0..4 {
+                    let freq = (column[base] as f64 + pseudocount) / total;
+                    column_scores[base] = (freq / background[base]).ln();
+                }
+                column_scores
+            })
+            .collect();
+
+        Self { name, scores }
+    }
+
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    /// The same PSSM read from the opposite strand: positions reversed and
+    /// base order complemented (A<->T, C<->G) at each position.
+    fn reverse_complement(&self) -> Self {
+        let scores = self
+            .scores
+            .iter()
+            .rev()
+            .map(|&[a, c, g, t]| [t, g, c, a])
+            .collect();
+
+        Self {
+            name: self.name.clone(),
+            scores,
+        }
+    }
+
+    fn score_window(&self, window: &[u8]) -> Option<f64> {
+        let mut total = 0.0;
+
+        for (position, &base) in window.iter().enumerate() {
+            let index = match base {
+                b'A' => 0,
+                b'C' => 1,
+                b'G' => 2,
+                b'T' => 3,
+                _ => return None,
+            };
+            total += self.scores[position][index];
+        }
+
+        Some(total)
+    }
+
+    /// Scans `sequence` on both strands, returning every hit whose summed
+    /// log-odds score is at least `threshold`.
+    pub fn scan(&self, sequence: &[u8], threshold: f64) -> Vec<MotifHit> {
+        if sequence.len() < self.len() {
+            return Vec::new();
+        }
+
+        let reverse = self.reverse_complement();
+        let mut hits = Vec::new();
+
+        for (strand, pssm) in [(Strand::Plus, self), (Strand::Minus, &reverse)] {
+            for (start, window) in sequence.windows(self.len()).enumerate() {
+                if let Some(score) = pssm.score_window(window) {
+                    if score >= threshold {
+                        hits.push(MotifHit {
+                            center_position: start + self.len() / 2,
+                            strand,
+                            score,
+                        });
+                    }
+                }
+            }
+        }
+
+        hits
+    }
+
+    /// Derives the minimum score whose null-distribution tail probability
+    /// under `background` is `<= p_value`, via the exact dynamic-programming
+    /// score distribution (column scores discretized at `bins_per_unit` bins
+    /// per score unit and convolved).
+    pub fn score_threshold_for_p_value(
+        &self,
+        background: [f64; 4],
+        p_value: f64,
+        bins_per_unit: f64,
+    ) -> f64 {
+        let discretized: Vec<[i64; 4]> = self
+            .scores
+            .iter()
+            .map(|column| {
+                let mut bins = [0i64; 4];
+                for base in 0..4 {
+                    bins[base] = (column[base] * bins_per_unit).round() as i64;
+                }
+                bins
+            })
+            .collect();
+
+        let mut distribution: HashMap<i64, f64> = HashMap::from([(0, 1.0)]);
+
+        for column in &discretized {
+            let mut next = HashMap::new();
+            for (&score, &prob) in &distribution {
+                for base in 0..4 {
+                    *next.entry(score + column[base]).or_insert(0.0) += prob * background[base];
+                }
+            }
+            distribution = next;
+        }
+
+        let mut bins: Vec<i64> = distribution.keys().copied().collect();
+        bins.sort_unstable_by(|a, b| b.cmp(a));
+
+        // Include bins from the top down while the tail probability stays
+        // within `p_value`; the bin that would push it over is excluded.
+        let mut cumulative = 0.0;
+        let mut threshold_bin = bins.first().map_or(0, |&top| top + 1);
+
+        for bin in bins {
+            let next_cumulative = cumulative + distribution[&bin];
+            if next_cumulative > p_value {
+                break;
+            }
+            cumulative = next_cumulative;
+            threshold_bin = bin;
+        }
+
+        threshold_bin as f64 / bins_per_unit
+    }
+}
+
+/// Loads every position frequency matrix from a JASPAR/MEME-style count
+/// matrix file (`>name` header, then 4 rows of counts, one per base).
+pub fn load_jaspar_pfms(path: &Path) -> Result<Vec<(String, Vec<[u32; 4]>)>> {
+    let content = fs::read_to_string(path)?;
+    let mut lines = content.lines();
+    let mut pfms = Vec::new();
+
+    while let Some(header) = lines.next() {
+        let header = header.trim();
+        if header.is_empty() {
+            continue;
+        }
+
+        if !header.starts_with('>') {
+            return Err(anyhow!("Expected a '>' header line, got \"{header}\""));
+        }
+
+        let name = header
+            .trim_start_matches('>')
+            .split_whitespace()
+            .last()
+            .unwrap_or(header)
+            .to_string();
+
+        let mut rows: HashMap<u8, Vec<u32>> = HashMap::new();
+
+        for _ in 0..4 {
+            let row = lines
+                .next()
+                .ok_or_else(|| anyhow!("Truncated matrix for \"{name}\""))?;
+
+            let mut tokens = row.split_whitespace();
+            let base = tokens
+                .next()
+                .ok_or_else(|| anyhow!("Empty matrix row for \"{name}\""))?
+                .bytes()
+                .next()
+                .ok_or_else(|| anyhow!("Empty base label for \"{name}\""))?;
+
+            let values: Result<Vec<u32>> = tokens
+                .filter(|token| *token != "[" && *token != "]")
+                .map(|token| {
+                    token
+                        .trim_matches(|c| c == '[' || c == ']')
+                        .parse::<u32>()
+                        .map_err(|e| anyhow!("Couldn't parse count \"{token}\" for \"{name}\": {e}"))
+                })
+                .collect();
+
+            rows.insert(base, values?);
+        }
+
+        for base in [b'A', b'C', b'G', b'T'] {
+            if !rows.contains_key(&base) {
+                return Err(anyhow!(
+                    "Matrix for \"{name}\" is missing row \"{}\"",
+                    base as char
+                ));
+            }
+        }
+
+        let len = rows[&b'A'].len();
+        for base in [b'C', b'G', b'T'] {
+            if rows[&base].len() != len {
+                return Err(anyhow!(
+                    "Matrix for \"{name}\" has mismatched row lengths"
+                ));
+            }
+        }
+
+        let counts = (0..len)
+            .map(|pos| [rows[&b'A'][pos], rows[&b'C'][pos], rows[&b'G'][pos], rows[&b'T'][pos]])
+            .collect();
+
+        pfms.push((name, counts));
+    }
+
+    Ok(pfms)
+}
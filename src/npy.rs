@@ -0,0 +1,37 @@
+use anyhow::Result;
+use ndarray::{Array1, Array2};
+use ndarray_npy::WriteNpyExt;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+
+/// Writes a stacked signal matrix to `<dir>/<name>.npy`, together with its
+/// 1-D offset vector at `<dir>/<name>_offsets.npy`.
+pub fn write_matrix(dir: &Path, name: &str, matrix: &Array2<f64>, offsets: &[i64]) -> Result<()> {
+    fs::create_dir_all(dir)?;
+
+    let offsets: Array1<i64> = offsets.iter().copied().collect();
+
+    let matrix_file = BufWriter::new(File::create(dir.join(format!("{name}.npy")))?);
+    matrix.write_npy(matrix_file)?;
+
+    let offsets_file = BufWriter::new(File::create(dir.join(format!("{name}_offsets.npy")))?);
+    offsets.write_npy(offsets_file)?;
+
+    Ok(())
+}
+
+/// Computes the mean of `matrix`'s `col`-th column, ignoring NaN entries.
+pub fn column_mean(matrix: &Array2<f64>, col: usize) -> f64 {
+    let mut sum = 0.0;
+    let mut count = 0u32;
+
+    for &v in matrix.column(col) {
+        if !v.is_nan() {
+            sum += v;
+            count += 1;
+        }
+    }
+
+    sum / count as f64
+}
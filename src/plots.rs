@@ -142,6 +142,37 @@ pub fn plot4(avg_affinity: Vec<(i32, f64)>, tf_name: &str) -> Result<()> {
     Ok(())
 }
 
+pub fn plot5(data: &[(usize, f64)]) -> Result<()> {
+    let figure = SVGBackend::new("promotor-tm.svg", (800, 250)).into_drawing_area();
+
+    figure.fill(&WHITE).unwrap();
+
+    let mut chart = ChartBuilder::on(&figure)
+        .margin(10)
+        .set_label_area_size(LabelAreaPosition::Left, 40)
+        .set_label_area_size(LabelAreaPosition::Bottom, 40)
+        .caption(
+            "Average Promotor Region Melting Temperature",
+            ("sans-serif", 14),
+        )
+        .build_cartesian_2d(-1000i64..100i64, 40.0f64..90.0f64)?;
+
+    // configure labels, axes, etc.
+    chart
+        .configure_mesh()
+        .disable_mesh()
+        .x_desc("bp")
+        .y_desc("Tm (°C)")
+        .draw()?;
+
+    chart.draw_series(LineSeries::new(
+        data.iter().map(|(a, b)| (*a as i64 - 1000, *b)),
+        ygb_color(1.0),
+    ))?;
+
+    Ok(())
+}
+
 fn ygb_color(idx: f64) -> plotters::style::RGBColor {
     let color = colorous::YELLOW_GREEN_BLUE.eval_continuous(idx).as_tuple();
     plotters::style::RGBColor {
@@ -0,0 +1,108 @@
+//! Nearest-neighbor melting-temperature (Tm) calculation, SantaLucia (1998)
+//! unified parameters.
+
+use anyhow::{anyhow, Result};
+
+/// Ideal gas constant, cal/(mol*K)
+const R: f64 = 1.987;
+
+/// ΔH° (kcal/mol) and ΔS° (cal/mol·K) for each of the 16 nearest-neighbor
+/// dinucleotides (only 10 are unique under reverse-complement symmetry).
+/// Indexed by `base_index(first) * 4 + base_index(second)`.
+const NN_PARAMS: [(f64, f64); 16] = [
+    // AA,    AC,    AG,    AT
+    (-7.9, -22.2), (-8.4, -22.4), (-7.8, -21.0), (-7.2, -20.4),
+    // CA,    CC,    CG,    CT
+    (-8.5, -22.7), (-8.0, -19.9), (-10.6, -27.2), (-7.8, -21.0),
+    // GA,    GC,    GG,    GT
+    (-8.2, -22.2), (-9.8, -24.4), (-8.0, -19.9), (-8.4, -22.4),
+    // TA,    TC,    TG,    TT
+    (-7.2, -21.3), (-8.2, -22.2), (-8.5, -22.7), (-7.9, -22.2),
+];
+
+/// ΔH° (kcal/mol) and ΔS° (cal/mol·K) helix initiation penalty for a
+/// terminal A·T or G·C base pair.
+const INIT_TERMINAL_GC: (f64, f64) = (0.1, -2.8);
+const INIT_TERMINAL_AT: (f64, f64) = (2.3, 4.1);
+
+fn base_index(base: u8) -> Result<usize> {
+    match base {
+        b'A' => Ok(0),
+        b'C' => Ok(1),
+        b'G' => Ok(2),
+        b'T' => Ok(3),
+        other => Err(anyhow!(
+            "Unexpected base '{}', expected A/C/G/T",
+            other as char
+        )),
+    }
+}
+
+/// Computes the nearest-neighbor melting temperature (°C) of `sequence`
+/// using the SantaLucia (1998) unified model. `strand_molarity` is the
+/// total strand concentration and `salt_molarity` the monovalent cation
+/// concentration, both in mol/L. Assumes a non-self-complementary duplex.
+pub fn nearest_neighbor_tm(
+    sequence: &[u8],
+    strand_molarity: f64,
+    salt_molarity: f64,
+) -> Result<f64> {
+    if sequence.len() < 2 {
+        return Err(anyhow!(
+            "sequence must be at least 2 bp long to compute a nearest-neighbor Tm"
+        ));
+    }
+
+    let indices: Vec<usize> = sequence
+        .iter()
+        .map(|&b| base_index(b))
+        .collect::<Result<_>>()?;
+
+    let mut delta_h = 0.0;
+    let mut delta_s = 0.0;
+
+    for pair in indices.windows(2) {
+        let (h, s) = NN_PARAMS[pair[0] * 4 + pair[1]];
+        delta_h += h;
+        delta_s += s;
+    }
+
+    for &end in &[indices[0], *indices.last().unwrap()] {
+        let (h, s) = if end == base_index(b'C').unwrap() || end == base_index(b'G').unwrap() {
+            INIT_TERMINAL_GC
+        } else {
+            INIT_TERMINAL_AT
+        };
+        delta_h += h;
+        delta_s += s;
+    }
+
+    const NON_SELF_COMPLEMENTARY_X: f64 = 4.0;
+
+    let mut tm = (1000.0 * delta_h) / (delta_s + R * (strand_molarity / NON_SELF_COMPLEMENTARY_X).ln())
+        - 273.15;
+    tm += 16.6 * salt_molarity.log10();
+
+    Ok(tm)
+}
+
+/// Slides a window over `sequence`, returning an iterator over
+/// `(window_center, melting_temp)`. Windows with non-A/C/G/T bytes are
+/// skipped.
+pub fn get_melting_temp(
+    sequence: &[u8],
+    window_size: usize,
+    step: usize,
+    strand_molarity: f64,
+    salt_molarity: f64,
+) -> impl Iterator<Item = (usize, f64)> + '_ {
+    sequence
+        .windows(window_size)
+        .step_by(step)
+        .enumerate()
+        .filter_map(move |(i, window)| {
+            nearest_neighbor_tm(window, strand_molarity, salt_molarity)
+                .ok()
+                .map(|tm| (i * step + window_size / 2, tm))
+        })
+}